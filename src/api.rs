@@ -1,38 +1,155 @@
-use bitcoin::Network;
-use serde::Deserialize;
-
-pub async fn get_address_balance(address: &str, network: Network) -> Result<f64, String> {
-    let base_url = match network {
-        Network::Bitcoin => "https://blockstream.info/api",
-        Network::Testnet => "https://blockstream.info/testnet/api",
-        _ => return Err("Unsupported network".to_string()),
-    };
-
-    let url = format!("{}/address/{}", base_url, address);
-    let response = reqwest::get(&url)
-        .await
-        .map_err(|e| format!("API request failed: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!("API error: {}", response.status()));
-    }
-
-    let data: AddressData = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse API response: {}", e))?;
-
-    let balance_satoshis = data.chain_stats.funded_txo_sum - data.chain_stats.spent_txo_sum;
-    Ok(balance_satoshis as f64 / 100_000_000.0)
-}
-
-#[derive(Deserialize)]
-struct AddressData {
-    chain_stats: ChainStats,
-}
-
-#[derive(Deserialize)]
-struct ChainStats {
-    funded_txo_sum: u64,
-    spent_txo_sum: u64,
-}
+use async_trait::async_trait;
+use bitcoin::Network;
+use serde::Deserialize;
+
+use crate::backend::{BalanceBackend, Utxo};
+
+/// Esplora-compatible REST backend (Blockstream, mempool.space, or any other
+/// instance exposing the same `/address/{addr}` and `/address/{addr}/utxo`
+/// API).
+pub struct EsploraBackend {
+    name: &'static str,
+    mainnet_base: &'static str,
+    testnet_base: &'static str,
+}
+
+impl EsploraBackend {
+    pub fn blockstream() -> Self {
+        Self {
+            name: "Blockstream",
+            mainnet_base: "https://blockstream.info/api",
+            testnet_base: "https://blockstream.info/testnet/api",
+        }
+    }
+
+    pub fn mempool_space() -> Self {
+        Self {
+            name: "mempool.space",
+            mainnet_base: "https://mempool.space/api",
+            testnet_base: "https://mempool.space/testnet/api",
+        }
+    }
+
+    fn base_url(&self, network: Network) -> Result<&'static str, String> {
+        match network {
+            Network::Bitcoin => Ok(self.mainnet_base),
+            Network::Testnet => Ok(self.testnet_base),
+            _ => Err("Unsupported network".to_string()),
+        }
+    }
+
+    async fn fetch_address_data(&self, address: &str, network: Network) -> Result<AddressData, String> {
+        let url = format!("{}/address/{}", self.base_url(network)?, address);
+        let response = reqwest::get(&url)
+            .await
+            .map_err(|e| format!("API request failed: {}", e))?;
+
+        check_rate_limited(&response, self.name)?;
+        if !response.status().is_success() {
+            return Err(format!("API error: {}", response.status()));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse API response: {}", e))
+    }
+}
+
+/// Returns a distinguishable "rate limited" error if `response` is an HTTP
+/// 429, so callers (see `wallet::scan_branch`) can tell a transient
+/// rate-limit from any other backend failure and stop the scan early
+/// without reporting it as an error.
+fn check_rate_limited(response: &reqwest::Response, backend_name: &str) -> Result<(), String> {
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(format!("{} rate limited this request (429)", backend_name));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl BalanceBackend for EsploraBackend {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn get_balance(&self, address: &str, network: Network) -> Result<f64, String> {
+        let data = self.fetch_address_data(address, network).await?;
+        let balance_satoshis = data.chain_stats.funded_txo_sum - data.chain_stats.spent_txo_sum;
+        Ok(balance_satoshis as f64 / 100_000_000.0)
+    }
+
+    async fn get_tx_count(&self, address: &str, network: Network) -> Result<u64, String> {
+        let data = self.fetch_address_data(address, network).await?;
+        Ok(data.chain_stats.funded_txo_count + data.chain_stats.spent_txo_count)
+    }
+
+    /// Overridden because `get_balance` and `get_tx_count` would otherwise
+    /// each independently call `fetch_address_data`, hitting the identical
+    /// `/address/{addr}` endpoint twice per address.
+    async fn get_balance_and_tx_count(
+        &self,
+        address: &str,
+        network: Network,
+    ) -> Result<(f64, u64), String> {
+        let data = self.fetch_address_data(address, network).await?;
+        let balance_satoshis = data.chain_stats.funded_txo_sum - data.chain_stats.spent_txo_sum;
+        let tx_count = data.chain_stats.funded_txo_count + data.chain_stats.spent_txo_count;
+        Ok((balance_satoshis as f64 / 100_000_000.0, tx_count))
+    }
+
+    async fn get_utxos(&self, address: &str, network: Network) -> Result<Vec<Utxo>, String> {
+        let url = format!("{}/address/{}/utxo", self.base_url(network)?, address);
+        let response = reqwest::get(&url)
+            .await
+            .map_err(|e| format!("API request failed: {}", e))?;
+
+        check_rate_limited(&response, self.name)?;
+        if !response.status().is_success() {
+            return Err(format!("API error: {}", response.status()));
+        }
+
+        let entries: Vec<EsploraUtxo> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse API response: {}", e))?;
+
+        Ok(entries
+            .into_iter()
+            .map(|u| Utxo {
+                txid: u.txid,
+                vout: u.vout,
+                value: u.value,
+                confirmed: u.status.confirmed,
+                block_height: u.status.block_height,
+            })
+            .collect())
+    }
+}
+
+#[derive(Deserialize)]
+struct AddressData {
+    chain_stats: ChainStats,
+}
+
+#[derive(Deserialize)]
+struct ChainStats {
+    funded_txo_count: u64,
+    funded_txo_sum: u64,
+    spent_txo_count: u64,
+    spent_txo_sum: u64,
+}
+
+#[derive(Deserialize)]
+struct EsploraUtxo {
+    txid: String,
+    vout: u32,
+    value: u64,
+    status: EsploraUtxoStatus,
+}
+
+#[derive(Deserialize)]
+struct EsploraUtxoStatus {
+    confirmed: bool,
+    block_height: Option<u64>,
+}
@@ -1,227 +1,487 @@
-use iced::{
-    alignment, executor, Alignment, Application, Color, Command, Element, Length, Theme,
-};
-use iced::widget::{
-    button, column, container, progress_bar, row, scrollable, text, text_input, Button, Column,
-    Container, ProgressBar, Row, Scrollable, Text, TextInput,
-};
-use crate::executor::CustomExecutor;
-use crate::messages::Message;
-use crate::wallet::{check_balances, AddressBalance};
-
-pub struct WalletBalanceApp {
-    xpub_input: String,
-    balances: Vec<AddressBalance>,
-    error: Option<String>,
-    loading: bool,
-    current_page: usize,
-    addresses_per_page: usize,
-    total_addresses_checked: usize,
-}
-
-impl WalletBalanceApp {
-    pub fn new() -> Self {
-        Self {
-            xpub_input: String::new(),
-            balances: Vec::new(),
-            error: None,
-            loading: false,
-            current_page: 0,
-            addresses_per_page: 10,
-            total_addresses_checked: 0,
-        }
-    }
-
-    fn calculate_address_range(&self) -> (usize, usize) {
-        let start = self.current_page * self.addresses_per_page;
-        let end = start + self.addresses_per_page;
-        (start, end)
-    }
-}
-
-impl Application for WalletBalanceApp {
-    type Message = Message;
-    type Executor = CustomExecutor;
-    type Flags = ();
-    type Theme = Theme;
-
-    fn new(_flags: ()) -> (Self, Command<Message>) {
-        (WalletBalanceApp::new(), Command::none())
-    }
-
-    fn title(&self) -> String {
-        String::from("Bitcoin Wallet Balance Discovery Tool")
-    }
-
-    fn update(&mut self, message: Message) -> Command<Message> {
-        match message {
-            Message::XpubInputChanged(value) => {
-                self.xpub_input = value;
-                self.current_page = 0;
-                self.balances.clear();
-                self.total_addresses_checked = 0;
-                Command::none()
-            }
-            Message::CheckBalance => {
-                self.loading = true;
-                self.error = None;
-                self.current_page = 0;
-                self.balances.clear();
-                self.total_addresses_checked = 0;
-                let xpub = self.xpub_input.clone();
-                let range = self.calculate_address_range();
-                Command::perform(
-                    async move { check_balances(&xpub, range.0, range.1).await },
-                    Message::BalanceResult,
-                )
-            }
-            Message::LoadMore => {
-                if !self.loading {
-                    self.loading = true;
-                    self.current_page += 1;
-                    let xpub = self.xpub_input.clone();
-                    let range = self.calculate_address_range();
-                    Command::perform(
-                        async move { check_balances(&xpub, range.0, range.1).await },
-                        Message::BalanceResult,
-                    )
-                } else {
-                    Command::none()
-                }
-            }
-            Message::BalanceResult(result) => {
-                self.loading = false;
-                match result {
-                    Ok(new_balances) => {
-                        self.total_addresses_checked += new_balances.len();
-                        self.balances.extend(new_balances);
-                    }
-                    Err(e) => {
-                        self.error = Some(format!("Error (showing partial results): {}", e));
-                    }
-                }
-                Command::none()
-            }
-        }
-    }
-
-    fn view(&self) -> Element<Message> {
-        let title = Text::new("Bitcoin Wallet Balance Discovery Tool")
-            .size(24)
-            .width(Length::Fill)
-            .horizontal_alignment(alignment::Horizontal::Center);
-
-        let input = TextInput::new(
-            "Enter extended public key (xpub/vpub)",
-            &self.xpub_input,
-        )
-        .on_input(Message::XpubInputChanged)
-        .padding(10)
-        .size(16);
-
-
-        let check_button = Button::new(Text::new("Check Balance"))
-            .on_press(Message::CheckBalance)
-            .padding(10);
-
-        let mut content = Column::new()
-            .push(title)
-            .push(input)
-            .push(check_button)
-            .spacing(15)
-            .padding(20)
-            .width(Length::Fill)
-            .align_items(Alignment::Center);
-
-        if self.loading {
-            content = content.push(
-                Column::new()
-                    .push(Text::new(format!(
-                        "Loading addresses {}-{}...",
-                        self.total_addresses_checked,
-                        self.total_addresses_checked + self.addresses_per_page
-                    )))
-                    .push(ProgressBar::new(0.0..=100.0, 50.0).width(Length::Fixed(300.0)))
-                    .spacing(10)
-                    .padding(10),
-            );
-        }
-
-        if let Some(error) = &self.error {
-            content = content.push(
-                Text::new(error)
-                    .size(14)
-                    .style(Color::from_rgb(0.8, 0.0, 0.0))
-                    .width(Length::Fill)
-                    .horizontal_alignment(alignment::Horizontal::Center),
-            );
-        }
-
-        if !self.balances.is_empty() {
-            let total: f64 = self.balances.iter().map(|b| b.balance).sum();
-
-            let header_row = Row::new()
-                .push(Text::new("Path").size(14).width(Length::FillPortion(2)))
-                .push(Text::new("Address").size(14).width(Length::FillPortion(5)))
-                .push(Text::new("Balance (BTC)").size(14).width(Length::FillPortion(2)))
-                .spacing(10)
-                .padding(5);
-
-            let balances_list = self.balances.iter().fold(
-                Column::new().push(header_row).spacing(2),
-                |col, balance| {
-                    col.push(
-                        Row::new()
-                            .push(
-                                Text::new(&balance.derivation_path)
-                                    .size(12)
-                                    .width(Length::FillPortion(2)),
-                            )
-                            .push(
-                                Text::new(&balance.address)
-                                    .size(12)
-                                    .width(Length::FillPortion(5)),
-                            )
-                            .push(
-                                Text::new(format!("{:.8} BTC", balance.balance))
-                                    .size(12)
-                                    .width(Length::FillPortion(2)),
-                            )
-                            .spacing(10)
-                            .padding(5),
-                    )
-                },
-            );
-
-            let scrollable_content = Scrollable::new(balances_list)
-                .height(Length::Fixed(250.0))
-                .width(Length::Fill);
-
-            let summary = Column::new()
-                .push(Text::new(format!(
-                    "Addresses checked: {}",
-                    self.total_addresses_checked
-                )))
-                .push(
-                    Text::new(format!("Total Balance: {:.8} BTC", total))
-                        .size(16)
-                        .style(Color::from_rgb(0.0, 0.5, 0.0)),
-                )
-                .spacing(5)
-                .padding(5);
-
-            content = content.push(scrollable_content).push(summary).push(
-                Button::new(Text::new("Load More Addresses"))
-                    .on_press(Message::LoadMore)
-                    .padding(5),
-            );
-        }
-
-        Container::new(content)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .center_x()
-            .padding(10)
-            .into()
-    }
-}
+use iced::{
+    alignment, executor, Alignment, Application, Color, Command, Element, Length, Subscription,
+    Theme,
+};
+use iced::widget::{
+    button, column, container, pick_list, progress_bar, row, scrollable, text, text_input, Button,
+    Column, Container, PickList, ProgressBar, Row, Scrollable, Text, TextInput,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+use wallet_balance_tool::api::EsploraBackend;
+use wallet_balance_tool::backend::{BackendChoice, BalanceBackend, FailoverBackend};
+use wallet_balance_tool::descriptor::ScriptType;
+use wallet_balance_tool::electrum::ElectrumBackend;
+use wallet_balance_tool::wallet::{check_balances, AddressBalance, ScanResult, DEFAULT_GAP_LIMIT};
+
+use crate::executor::CustomExecutor;
+use crate::messages::Message;
+
+/// State driving the [`WalletBalanceApp::subscription`] that relays
+/// in-progress address counts from a running scan. `Listening` holds the
+/// receiver half of the channel `CheckBalance` created; `Idle` means no
+/// scan is running, so the subscription does nothing.
+enum ProgressState {
+    Idle,
+    Listening(Arc<Mutex<mpsc::UnboundedReceiver<usize>>>),
+}
+
+pub struct WalletBalanceApp {
+    xpub_input: String,
+    gap_limit_input: String,
+    gap_limit: usize,
+    script_type: ScriptType,
+    backend_choice: BackendChoice,
+    electrum_server_input: String,
+    balances: Vec<AddressBalance>,
+    expanded_rows: HashSet<usize>,
+    error: Option<String>,
+    loading: bool,
+    scan_complete: Option<bool>,
+    progress: ProgressState,
+    addresses_checked: usize,
+}
+
+impl WalletBalanceApp {
+    pub fn new() -> Self {
+        Self {
+            xpub_input: String::new(),
+            gap_limit_input: DEFAULT_GAP_LIMIT.to_string(),
+            gap_limit: DEFAULT_GAP_LIMIT,
+            script_type: ScriptType::P2wpkh,
+            backend_choice: BackendChoice::Blockstream,
+            electrum_server_input: String::new(),
+            balances: Vec::new(),
+            expanded_rows: HashSet::new(),
+            error: None,
+            loading: false,
+            scan_complete: None,
+            progress: ProgressState::Idle,
+            addresses_checked: 0,
+        }
+    }
+}
+
+/// Builds the backend chain implied by the GUI's backend selection: the
+/// chosen backend first, falling back to the other Esplora provider (or to
+/// Blockstream, for Electrum) if the primary choice fails. `electrum_server` is only used for
+/// `BackendChoice::Electrum`.
+///
+/// Connecting to an Electrum server blocks on a TCP connect/handshake, so
+/// it's run via `spawn_blocking` rather than inline -- this function is
+/// always called from within the async block handed to `Command::perform`,
+/// never directly from `update()`, so that block never stalls the GUI
+/// thread.
+async fn build_backend(
+    backend_choice: BackendChoice,
+    electrum_server: String,
+) -> Result<Arc<dyn BalanceBackend>, String> {
+    let mut backends: Vec<Box<dyn BalanceBackend>> = Vec::new();
+
+    match backend_choice {
+        BackendChoice::Blockstream => {
+            backends.push(Box::new(EsploraBackend::blockstream()));
+            backends.push(Box::new(EsploraBackend::mempool_space()));
+        }
+        BackendChoice::MempoolSpace => {
+            backends.push(Box::new(EsploraBackend::mempool_space()));
+            backends.push(Box::new(EsploraBackend::blockstream()));
+        }
+        BackendChoice::Electrum => {
+            let electrum = tokio::task::spawn_blocking(move || ElectrumBackend::connect(&electrum_server))
+                .await
+                .map_err(|e| format!("Electrum connect task failed: {}", e))??;
+            backends.push(Box::new(electrum));
+            backends.push(Box::new(EsploraBackend::blockstream()));
+        }
+    }
+
+    Ok(Arc::new(FailoverBackend::new(backends)))
+}
+
+impl Application for WalletBalanceApp {
+    type Message = Message;
+    type Executor = CustomExecutor;
+    type Flags = ();
+    type Theme = Theme;
+
+    fn new(_flags: ()) -> (Self, Command<Message>) {
+        (WalletBalanceApp::new(), Command::none())
+    }
+
+    fn title(&self) -> String {
+        String::from("Bitcoin Wallet Balance Discovery Tool")
+    }
+
+    /// Relays [`Message::ScanProgress`] from the channel `CheckBalance` wired
+    /// up, so the progress bar reflects addresses actually checked instead
+    /// of a static estimate. Returns `Subscription::none()` once the scan
+    /// finishes and `self.progress` goes back to `Idle`.
+    fn subscription(&self) -> Subscription<Message> {
+        match &self.progress {
+            ProgressState::Idle => Subscription::none(),
+            ProgressState::Listening(rx) => {
+                let rx = Arc::clone(rx);
+                iced::subscription::unfold("scan-progress", rx, |rx| async move {
+                    let next = rx.lock().await.recv().await;
+                    match next {
+                        Some(count) => (Message::ScanProgress(count), rx),
+                        None => std::future::pending().await,
+                    }
+                })
+            }
+        }
+    }
+
+    fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::XpubInputChanged(value) => {
+                self.xpub_input = value;
+                self.balances.clear();
+                self.scan_complete = None;
+                Command::none()
+            }
+            Message::GapLimitChanged(value) => {
+                if let Ok(limit) = value.parse::<usize>() {
+                    self.gap_limit = limit;
+                }
+                self.gap_limit_input = value;
+                Command::none()
+            }
+            Message::ScriptTypeSelected(script_type) => {
+                self.script_type = script_type;
+                Command::none()
+            }
+            Message::BackendSelected(choice) => {
+                self.backend_choice = choice;
+                Command::none()
+            }
+            Message::ElectrumServerChanged(value) => {
+                self.electrum_server_input = value;
+                Command::none()
+            }
+            Message::ToggleUtxos(index) => {
+                if !self.expanded_rows.remove(&index) {
+                    self.expanded_rows.insert(index);
+                }
+                Command::none()
+            }
+            Message::CheckBalance => {
+                self.loading = true;
+                self.error = None;
+                self.balances.clear();
+                self.expanded_rows.clear();
+                self.scan_complete = None;
+                self.addresses_checked = 0;
+                let xpub = self.xpub_input.clone();
+                let gap_limit = self.gap_limit;
+                let script_type = self.script_type;
+                let backend_choice = self.backend_choice;
+                let electrum_server = self.electrum_server_input.clone();
+
+                let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+                self.progress = ProgressState::Listening(Arc::new(Mutex::new(progress_rx)));
+
+                Command::perform(
+                    async move {
+                        let backend = build_backend(backend_choice, electrum_server).await?;
+                        check_balances(&xpub, gap_limit, script_type, backend, Some(progress_tx))
+                            .await
+                    },
+                    Message::BalanceResult,
+                )
+            }
+            Message::BalanceResult(result) => {
+                self.loading = false;
+                self.progress = ProgressState::Idle;
+                match result {
+                    Ok(ScanResult { balances, complete, error }) => {
+                        self.addresses_checked = balances.len();
+                        let has_partial_results = !balances.is_empty();
+                        self.balances = balances;
+                        self.scan_complete = Some(complete);
+                        if let Some(e) = error {
+                            self.error = Some(if has_partial_results {
+                                format!("Error (showing partial results): {}", e)
+                            } else {
+                                format!("Error: {}", e)
+                            });
+                        }
+                    }
+                    Err(e) => {
+                        self.error = Some(format!("Error: {}", e));
+                    }
+                }
+                Command::none()
+            }
+            Message::ScanProgress(count) => {
+                self.addresses_checked = count;
+                Command::none()
+            }
+            #[cfg(feature = "hardware")]
+            Message::ImportFromDevice => {
+                self.error = None;
+                let script_type = self.script_type;
+                Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            wallet_balance_tool::hardware::import_xpub(script_type)
+                        })
+                        .await
+                        .unwrap_or_else(|e| Err(format!("Device import task failed: {}", e)))
+                    },
+                    Message::DeviceXpubImported,
+                )
+            }
+            #[cfg(feature = "hardware")]
+            Message::DeviceXpubImported(result) => {
+                match result {
+                    Ok(xpub) => self.xpub_input = xpub,
+                    Err(e) => self.error = Some(format!("Device import failed: {}", e)),
+                }
+                Command::none()
+            }
+        }
+    }
+
+    fn view(&self) -> Element<Message> {
+        let title = Text::new("Bitcoin Wallet Balance Discovery Tool")
+            .size(24)
+            .width(Length::Fill)
+            .horizontal_alignment(alignment::Horizontal::Center);
+
+        let input = TextInput::new(
+            "Enter xpub/ypub/zpub or an output descriptor",
+            &self.xpub_input,
+        )
+        .on_input(Message::XpubInputChanged)
+        .padding(10)
+        .size(16);
+
+        #[cfg(feature = "hardware")]
+        let input_row = {
+            let import_button = Button::new(Text::new("Import from device"))
+                .on_press(Message::ImportFromDevice)
+                .padding(10);
+            Row::new()
+                .push(input)
+                .push(import_button)
+                .spacing(10)
+                .align_items(Alignment::Center)
+        };
+        #[cfg(not(feature = "hardware"))]
+        let input_row = Row::new().push(input);
+
+        let gap_limit_input = TextInput::new("Gap limit", &self.gap_limit_input)
+            .on_input(Message::GapLimitChanged)
+            .padding(10)
+            .size(16)
+            .width(Length::Fixed(100.0));
+
+        let script_type_picker = PickList::new(
+            &ScriptType::ALL[..],
+            Some(self.script_type),
+            Message::ScriptTypeSelected,
+        )
+        .padding(10);
+
+        let backend_picker = PickList::new(
+            &BackendChoice::ALL[..],
+            Some(self.backend_choice),
+            Message::BackendSelected,
+        )
+        .padding(10);
+
+        let mut backend_row = Row::new()
+            .push(Text::new("Balance backend:").size(14))
+            .push(backend_picker)
+            .spacing(10)
+            .align_items(Alignment::Center);
+
+        if self.backend_choice == BackendChoice::Electrum {
+            let electrum_input = TextInput::new("host:port", &self.electrum_server_input)
+                .on_input(Message::ElectrumServerChanged)
+                .padding(10)
+                .size(16);
+            backend_row = backend_row.push(electrum_input);
+        }
+
+        let check_button = Button::new(Text::new("Check Balance"))
+            .on_press(Message::CheckBalance)
+            .padding(10);
+
+        let mut content = Column::new()
+            .push(title)
+            .push(input_row)
+            .push(
+                Row::new()
+                    .push(Text::new("Gap limit:").size(14))
+                    .push(gap_limit_input)
+                    .push(Text::new("Script type (used unless a descriptor or ypub/zpub-style prefix specifies one):").size(14))
+                    .push(script_type_picker)
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+            )
+            .push(backend_row)
+            .push(check_button)
+            .spacing(15)
+            .padding(20)
+            .width(Length::Fill)
+            .align_items(Alignment::Center);
+
+        if self.loading {
+            // Each branch checks at least `gap_limit` addresses (more if any
+            // are used), so `2 * gap_limit` is the fewest a complete scan
+            // checks; the bar is sized against that and capped at 95%, where
+            // it stays while a wallet with used addresses runs past it.
+            let estimated_total = (self.gap_limit * 2).max(1) as f32;
+            let progress = (self.addresses_checked as f32 / estimated_total * 100.0).min(95.0);
+
+            content = content.push(
+                Column::new()
+                    .push(Text::new(format!(
+                        "Scanning... {} addresses checked so far",
+                        self.addresses_checked
+                    )))
+                    .push(ProgressBar::new(0.0..=100.0, progress).width(Length::Fixed(300.0)))
+                    .spacing(10)
+                    .padding(10),
+            );
+        }
+
+        if let Some(error) = &self.error {
+            content = content.push(
+                Text::new(error)
+                    .size(14)
+                    .style(Color::from_rgb(0.8, 0.0, 0.0))
+                    .width(Length::Fill)
+                    .horizontal_alignment(alignment::Horizontal::Center),
+            );
+        }
+
+        if !self.balances.is_empty() {
+            let total: f64 = self.balances.iter().map(|b| b.balance).sum();
+
+            let header_row = Row::new()
+                .push(Text::new("").width(Length::Fixed(20.0)))
+                .push(Text::new("Path").size(14).width(Length::FillPortion(2)))
+                .push(Text::new("Address").size(14).width(Length::FillPortion(5)))
+                .push(Text::new("Balance (BTC)").size(14).width(Length::FillPortion(2)))
+                .spacing(10)
+                .padding(5);
+
+            let balances_list = self.balances.iter().enumerate().fold(
+                Column::new().push(header_row).spacing(2),
+                |col, (index, balance)| {
+                    let expanded = self.expanded_rows.contains(&index);
+                    let toggle = Text::new(if expanded { "▾" } else { "▸" }).size(12);
+
+                    let row = Button::new(
+                        Row::new()
+                            .push(toggle.width(Length::Fixed(20.0)))
+                            .push(
+                                Text::new(&balance.derivation_path)
+                                    .size(12)
+                                    .width(Length::FillPortion(2)),
+                            )
+                            .push(
+                                Text::new(&balance.address)
+                                    .size(12)
+                                    .width(Length::FillPortion(5)),
+                            )
+                            .push(
+                                Text::new(format!("{:.8} BTC", balance.balance))
+                                    .size(12)
+                                    .width(Length::FillPortion(2)),
+                            )
+                            .spacing(10)
+                            .padding(5),
+                    )
+                    .on_press(Message::ToggleUtxos(index))
+                    .padding(0);
+
+                    let mut entry = Column::new().push(row);
+
+                    if expanded {
+                        if balance.utxos.is_empty() {
+                            entry = entry.push(
+                                Text::new("No UTXOs at this address")
+                                    .size(11)
+                                    .width(Length::Fill),
+                            );
+                        } else {
+                            for utxo in &balance.utxos {
+                                let status = if utxo.confirmed {
+                                    match utxo.block_height {
+                                        Some(height) => format!("confirmed (block {})", height),
+                                        None => "confirmed".to_string(),
+                                    }
+                                } else {
+                                    "unconfirmed".to_string()
+                                };
+
+                                entry = entry.push(
+                                    Row::new()
+                                        .push(Text::new("").width(Length::Fixed(20.0)))
+                                        .push(
+                                            Text::new(format!("{}:{}", utxo.txid, utxo.vout))
+                                                .size(11)
+                                                .width(Length::FillPortion(7)),
+                                        )
+                                        .push(
+                                            Text::new(format!(
+                                                "{:.8} BTC",
+                                                utxo.value as f64 / 100_000_000.0
+                                            ))
+                                            .size(11)
+                                            .width(Length::FillPortion(2)),
+                                        )
+                                        .push(Text::new(status).size(11).width(Length::FillPortion(2)))
+                                        .spacing(10)
+                                        .padding(5),
+                                );
+                            }
+                        }
+                    }
+
+                    col.push(entry)
+                },
+            );
+
+            let scrollable_content = Scrollable::new(balances_list)
+                .height(Length::Fixed(250.0))
+                .width(Length::Fill);
+
+            let status = match self.scan_complete {
+                Some(true) => "Scan complete (gap limit reached on both branches)".to_string(),
+                Some(false) => "Scan stopped early — results may be incomplete".to_string(),
+                None => String::new(),
+            };
+
+            let summary = Column::new()
+                .push(Text::new(format!(
+                    "Addresses checked: {}",
+                    self.balances.len()
+                )))
+                .push(
+                    Text::new(format!("Total Balance: {:.8} BTC", total))
+                        .size(16)
+                        .style(Color::from_rgb(0.0, 0.5, 0.0)),
+                )
+                .push(Text::new(status).size(12))
+                .spacing(5)
+                .padding(5);
+
+            content = content.push(scrollable_content).push(summary);
+        }
+
+        Container::new(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .padding(10)
+            .into()
+    }
+}
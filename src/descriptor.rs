@@ -0,0 +1,286 @@
+use bitcoin::{base58, secp256k1::{All, Secp256k1}, Address, Network, PublicKey};
+
+/// Which output script a branch of addresses should be derived as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    /// BIP44 legacy P2PKH.
+    P2pkh,
+    /// BIP49 nested SegWit, P2SH-wrapped P2WPKH.
+    P2shP2wpkh,
+    /// BIP84 native SegWit P2WPKH.
+    P2wpkh,
+    /// BIP86 Taproot P2TR (key-path spend only).
+    P2tr,
+}
+
+impl ScriptType {
+    pub const ALL: [ScriptType; 4] = [
+        ScriptType::P2pkh,
+        ScriptType::P2shP2wpkh,
+        ScriptType::P2wpkh,
+        ScriptType::P2tr,
+    ];
+
+    pub fn derive_address(
+        &self,
+        public_key: &PublicKey,
+        network: Network,
+        secp: &Secp256k1<All>,
+    ) -> Result<Address, String> {
+        match self {
+            ScriptType::P2pkh => Ok(Address::p2pkh(public_key, network)),
+            ScriptType::P2shP2wpkh => Address::p2shwpkh(public_key, network)
+                .map_err(|e| format!("Address generation error: {}", e)),
+            ScriptType::P2wpkh => Address::p2wpkh(public_key, network)
+                .map_err(|e| format!("Address generation error: {}", e)),
+            ScriptType::P2tr => {
+                let (x_only, _parity) = public_key.inner.x_only_public_key(secp);
+                Ok(Address::p2tr(secp, x_only, None, network))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ScriptType::P2pkh => "Legacy (P2PKH, BIP44)",
+            ScriptType::P2shP2wpkh => "Nested SegWit (P2SH-P2WPKH, BIP49)",
+            ScriptType::P2wpkh => "Native SegWit (P2WPKH, BIP84)",
+            ScriptType::P2tr => "Taproot (P2TR, BIP86)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An account-level key expression extracted from either a bare extended
+/// public key or a full output descriptor string, plus the script type it
+/// implies (if any).
+pub struct Descriptor {
+    pub script_type: Option<ScriptType>,
+    pub xpub: String,
+    pub origin: Option<String>,
+}
+
+/// Parses either a bare xpub/ypub/zpub (and testnet equivalents) or a full
+/// output descriptor such as `wpkh([fingerprint/84h/0h/0h]xpub.../0/*)`.
+/// Bare keys carry no explicit script type; the caller falls back to a
+/// user-selected default or the SLIP-132 version-byte hint.
+pub fn parse(input: &str) -> Result<Descriptor, String> {
+    let input = strip_checksum(input.trim());
+
+    if let Some(inner) = strip_wrapper(input, "sh(wpkh(", "))") {
+        let (origin, xpub) = parse_key_expression(inner);
+        return Ok(Descriptor { script_type: Some(ScriptType::P2shP2wpkh), xpub, origin });
+    }
+    if let Some(inner) = strip_wrapper(input, "wpkh(", ")") {
+        let (origin, xpub) = parse_key_expression(inner);
+        return Ok(Descriptor { script_type: Some(ScriptType::P2wpkh), xpub, origin });
+    }
+    if let Some(inner) = strip_wrapper(input, "pkh(", ")") {
+        let (origin, xpub) = parse_key_expression(inner);
+        return Ok(Descriptor { script_type: Some(ScriptType::P2pkh), xpub, origin });
+    }
+    if let Some(inner) = strip_wrapper(input, "tr(", ")") {
+        let (origin, xpub) = parse_key_expression(inner);
+        return Ok(Descriptor { script_type: Some(ScriptType::P2tr), xpub, origin });
+    }
+
+    // Not a descriptor: treat the whole string as a bare key expression.
+    let (origin, xpub) = parse_key_expression(input);
+    Ok(Descriptor { script_type: None, xpub, origin })
+}
+
+fn strip_wrapper<'a>(input: &'a str, prefix: &str, suffix: &str) -> Option<&'a str> {
+    input.strip_prefix(prefix)?.strip_suffix(suffix)
+}
+
+/// Strips a trailing BIP-380 checksum (`#` followed by 8 bech32 characters),
+/// as produced by e.g. Bitcoin Core's `getdescriptorinfo`/`listdescriptors`.
+/// The checksum isn't recomputed or verified here -- only removed so the
+/// wrapper-matching in `parse` isn't thrown off by it.
+fn strip_checksum(input: &str) -> &str {
+    match input.rfind('#') {
+        Some(pos) => &input[..pos],
+        None => input,
+    }
+}
+
+/// Splits `[fingerprint/84h/0h/0h]xpub.../0/*` into the origin info
+/// (`fingerprint/84h/0h/0h`) and the bare extended public key.
+fn parse_key_expression(expr: &str) -> (Option<String>, String) {
+    let (origin, rest) = if let Some(rest) = expr.strip_prefix('[') {
+        match rest.find(']') {
+            Some(end) => (Some(rest[..end].to_string()), &rest[end + 1..]),
+            None => (None, expr),
+        }
+    } else {
+        (None, expr)
+    };
+
+    let xpub = rest.split('/').next().unwrap_or(rest).to_string();
+    (origin, xpub)
+}
+
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xCF];
+
+/// Normalizes any of the single-sig SLIP-132 extended-key prefixes (mainnet
+/// `xpub`/`ypub`/`zpub`, testnet `tpub`/`upub`/`vpub`) to the canonical
+/// `xpub`/`tpub` form `ExtendedPubKey` understands, and reports the script
+/// type the prefix implies (BIP49 for y/u, BIP84 for z/v). `xpub`/`tpub`
+/// themselves carry no script-type hint.
+pub fn normalize_xpub(input: &str) -> Result<(Network, String, Option<ScriptType>), String> {
+    // `get(..4)` (rather than indexing `input[..4]` directly) returns `None`
+    // both when `input` is too short and when byte offset 4 falls inside a
+    // multi-byte UTF-8 character, so malformed input from e.g. the GUI's
+    // xpub text field is reported as the usual error instead of panicking.
+    let prefix = input
+        .get(..4)
+        .ok_or_else(|| "Extended public key is too short".to_string())?;
+
+    let (network, canonical_version, hint) = match prefix {
+        "xpub" => (Network::Bitcoin, XPUB_VERSION, None),
+        "ypub" => (Network::Bitcoin, XPUB_VERSION, Some(ScriptType::P2shP2wpkh)),
+        "zpub" => (Network::Bitcoin, XPUB_VERSION, Some(ScriptType::P2wpkh)),
+        "tpub" => (Network::Testnet, TPUB_VERSION, None),
+        "upub" => (Network::Testnet, TPUB_VERSION, Some(ScriptType::P2shP2wpkh)),
+        "vpub" => (Network::Testnet, TPUB_VERSION, Some(ScriptType::P2wpkh)),
+        other => {
+            return Err(format!(
+                "Unsupported extended public key format '{}'. Must start with xpub/ypub/zpub or tpub/upub/vpub",
+                other
+            ))
+        }
+    };
+
+    if prefix == "xpub" || prefix == "tpub" {
+        return Ok((network, input.to_string(), hint));
+    }
+
+    // `from_check` already verifies and strips the 4-byte checksum, so
+    // `decoded` is just `version (4 bytes) ++ key material (74 bytes)`.
+    let decoded = base58::from_check(input)
+        .map_err(|e| format!("Failed to decode extended public key: {}", e))?;
+
+    if decoded.len() != 78 {
+        return Err("Invalid extended public key length".to_string());
+    }
+
+    // Extract the key material (everything except the version bytes).
+    let key_material = &decoded[4..];
+
+    let mut modified = Vec::with_capacity(78);
+    modified.extend_from_slice(&canonical_version);
+    modified.extend_from_slice(key_material);
+
+    // `encode_check` computes and appends the checksum itself.
+    let normalized = base58::encode_check(&modified);
+
+    Ok((network, normalized, hint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZPUB_VERSION: [u8; 4] = [0x04, 0xB2, 0x47, 0x46];
+    const VPUB_VERSION: [u8; 4] = [0x04, 0x5F, 0x1C, 0xF6];
+
+    /// A syntactically valid 74-byte BIP32 payload (depth, parent
+    /// fingerprint, child number, chain code, compressed public key) with
+    /// arbitrary-but-fixed bytes. Not a real key -- only used to check that
+    /// re-encoding preserves it byte-for-byte.
+    fn dummy_key_material() -> [u8; 74] {
+        let mut material = [0u8; 74];
+        material[0] = 3;
+        material[1..5].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+        material[5..9].copy_from_slice(&[0x80, 0x00, 0x00, 0x02]);
+        for (i, byte) in material[9..41].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        material[41] = 0x02;
+        for (i, byte) in material[42..74].iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_add(1);
+        }
+        material
+    }
+
+    fn encode_with_version(version: [u8; 4], material: &[u8; 74]) -> String {
+        let mut payload = Vec::with_capacity(78);
+        payload.extend_from_slice(&version);
+        payload.extend_from_slice(material);
+        base58::encode_check(&payload)
+    }
+
+    #[test]
+    fn normalize_zpub_preserves_key_material() {
+        let material = dummy_key_material();
+        let zpub = encode_with_version(ZPUB_VERSION, &material);
+        assert!(zpub.starts_with("zpub"));
+
+        let (network, normalized, hint) = normalize_xpub(&zpub).expect("should decode");
+
+        assert_eq!(network, Network::Bitcoin);
+        assert_eq!(hint, Some(ScriptType::P2wpkh));
+
+        let xpub_bytes = base58::from_check(&normalized).expect("should re-decode");
+        assert_eq!(&xpub_bytes[..4], &XPUB_VERSION[..]);
+        // Everything past the version bytes -- depth, parent fingerprint,
+        // child number, chain code, and the public key -- must survive
+        // unchanged; this is the exact byte range the original bug clipped.
+        assert_eq!(&xpub_bytes[4..], &material[..]);
+    }
+
+    #[test]
+    fn normalize_vpub_preserves_key_material_and_hints_wpkh_on_testnet() {
+        let material = dummy_key_material();
+        let vpub = encode_with_version(VPUB_VERSION, &material);
+        assert!(vpub.starts_with("vpub"));
+
+        let (network, normalized, hint) = normalize_xpub(&vpub).expect("should decode");
+
+        assert_eq!(network, Network::Testnet);
+        assert_eq!(hint, Some(ScriptType::P2wpkh));
+
+        let tpub_bytes = base58::from_check(&normalized).expect("should re-decode");
+        assert_eq!(&tpub_bytes[..4], &TPUB_VERSION[..]);
+        assert_eq!(&tpub_bytes[4..], &material[..]);
+    }
+
+    #[test]
+    fn xpub_passes_through_unchanged() {
+        let material = dummy_key_material();
+        let xpub = encode_with_version(XPUB_VERSION, &material);
+
+        let (network, normalized, hint) = normalize_xpub(&xpub).expect("should pass through");
+
+        assert_eq!(network, Network::Bitcoin);
+        assert_eq!(hint, None);
+        assert_eq!(normalized, xpub);
+    }
+
+    #[test]
+    fn normalize_xpub_rejects_multibyte_input_without_panicking() {
+        // Byte offset 4 falls inside the 'é' (2-byte UTF-8), so naively
+        // slicing `&input[..4]` panics with "byte index 4 is not a char
+        // boundary"; this must instead produce the usual error.
+        let result = normalize_xpub("xpuébogus");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parse_strips_bip380_checksum_suffix() {
+        let material = dummy_key_material();
+        let xpub = encode_with_version(XPUB_VERSION, &material);
+        let descriptor = parse(&format!("wpkh([deadbeef/84h/0h/0h]{}/0/*)#abcd1234", xpub));
+
+        // If the checksum weren't stripped, `strip_wrapper`'s suffix match
+        // on the closing paren would fail and this would fall through to
+        // the broken bare-key path instead.
+        let descriptor = descriptor.expect("should parse");
+        assert_eq!(descriptor.script_type, Some(ScriptType::P2wpkh));
+        assert_eq!(descriptor.xpub, xpub);
+        assert_eq!(descriptor.origin.as_deref(), Some("deadbeef/84h/0h/0h"));
+    }
+}
@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use bitcoin::{Address, Network};
+use electrum_client::ElectrumApi;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::{BalanceBackend, Utxo};
+
+/// Electrum protocol backend, for users who'd rather point at their own
+/// node (or any `host:port` Electrum server) than a public Esplora instance.
+///
+/// Electrum addresses queries by "scripthash" (the reversed SHA256 of the
+/// output script) rather than by address string; `electrum_client` computes
+/// that internally for us given the address's script pubkey.
+///
+/// `electrum_client::Client` is a blocking socket client, so every call that
+/// touches it -- including connecting -- is moved onto the blocking thread
+/// pool with `tokio::task::spawn_blocking` rather than run inline, which
+/// would otherwise stall a tokio worker (or the GUI thread, for `connect`)
+/// for the full round-trip.
+pub struct ElectrumBackend {
+    server: String,
+    client: Arc<Mutex<electrum_client::Client>>,
+}
+
+impl ElectrumBackend {
+    /// Connects to `server` (`host:port`). This blocks on the TCP
+    /// connect/handshake, so callers on an async/GUI thread should run it
+    /// via `tokio::task::spawn_blocking`.
+    pub fn connect(server: &str) -> Result<Self, String> {
+        let client = electrum_client::Client::new(server)
+            .map_err(|e| format!("Failed to connect to Electrum server {}: {}", server, e))?;
+        Ok(Self {
+            server: server.to_string(),
+            client: Arc::new(Mutex::new(client)),
+        })
+    }
+
+    fn script_pubkey(&self, address: &str, network: Network) -> Result<bitcoin::Script, String> {
+        let address = Address::from_str(address)
+            .map_err(|e| format!("Invalid address: {}", e))?;
+        if address.network != network {
+            return Err("Address does not match the expected network".to_string());
+        }
+        Ok(address.script_pubkey())
+    }
+
+    /// Runs a blocking Electrum call on the blocking thread pool, holding
+    /// the lock only for the duration of the call.
+    async fn run_blocking<T, F>(&self, call: F) -> Result<T, String>
+    where
+        F: FnOnce(&electrum_client::Client) -> Result<T, String> + Send + 'static,
+        T: Send + 'static,
+    {
+        let client = Arc::clone(&self.client);
+        tokio::task::spawn_blocking(move || {
+            let client = client.lock().map_err(|e| e.to_string())?;
+            call(&client)
+        })
+        .await
+        .map_err(|e| format!("Electrum task failed: {}", e))?
+    }
+}
+
+#[async_trait]
+impl BalanceBackend for ElectrumBackend {
+    fn name(&self) -> &str {
+        &self.server
+    }
+
+    async fn get_balance(&self, address: &str, network: Network) -> Result<f64, String> {
+        let script = self.script_pubkey(address, network)?;
+        self.run_blocking(move |client| {
+            let balance = client
+                .script_get_balance(&script)
+                .map_err(|e| format!("Electrum request failed: {}", e))?;
+            Ok((balance.confirmed as f64) / 100_000_000.0)
+        })
+        .await
+    }
+
+    async fn get_tx_count(&self, address: &str, network: Network) -> Result<u64, String> {
+        let script = self.script_pubkey(address, network)?;
+        self.run_blocking(move |client| {
+            let history = client
+                .script_get_history(&script)
+                .map_err(|e| format!("Electrum request failed: {}", e))?;
+            Ok(history.len() as u64)
+        })
+        .await
+    }
+
+    async fn get_utxos(&self, address: &str, network: Network) -> Result<Vec<Utxo>, String> {
+        let script = self.script_pubkey(address, network)?;
+        self.run_blocking(move |client| {
+            let unspent = client
+                .script_list_unspent(&script)
+                .map_err(|e| format!("Electrum request failed: {}", e))?;
+
+            Ok(unspent
+                .into_iter()
+                .map(|u| Utxo {
+                    txid: u.tx_hash.to_string(),
+                    vout: u.tx_pos as u32,
+                    value: u.value,
+                    confirmed: u.height > 0,
+                    block_height: if u.height > 0 { Some(u.height as u64) } else { None },
+                })
+                .collect())
+        })
+        .await
+    }
+}
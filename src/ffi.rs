@@ -0,0 +1,99 @@
+//! C-compatible FFI surface, generated into a header with `cbindgen`. Lets
+//! the scanning engine be embedded in CLI tools, test harnesses, or
+//! mobile/dart hosts that can't link against `iced`/`tokio`'s GUI executor.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::sync::Arc;
+
+use crate::api::EsploraBackend;
+use crate::backend::{BalanceBackend, FailoverBackend};
+use crate::descriptor::ScriptType;
+use crate::wallet::check_balances;
+
+/// Runs a gap-limit balance scan against the public Esplora backends and
+/// writes the result as an owned, NUL-terminated JSON string to `*out_json`.
+/// On success the JSON is `{"complete": bool, "balances": [...], "error":
+/// string|null}`, where `error` is set when the scan stopped early but
+/// still carries partial `balances`; on a failure before scanning could
+/// start it's `{"error": "..."}` with no `balances` key. Returns `0` on
+/// success (including a scan-level error reported as JSON) or a negative
+/// value if the call could not be made at all (bad pointers, invalid UTF-8,
+/// runtime startup failure).
+///
+/// # Safety
+/// `xpub` must be a valid, NUL-terminated C string that outlives the call.
+/// `out_json` must be a valid, non-null pointer to a `*mut c_char`. The
+/// string written to it must later be freed with [`wbt_string_free`] and
+/// must not be freed any other way.
+#[no_mangle]
+pub unsafe extern "C" fn wbt_check_balances(
+    xpub: *const c_char,
+    gap_limit: usize,
+    out_json: *mut *mut c_char,
+) -> c_int {
+    if xpub.is_null() || out_json.is_null() {
+        return -1;
+    }
+
+    let xpub = match CStr::from_ptr(xpub).to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return -1,
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(_) => return -1,
+    };
+
+    let json = runtime.block_on(scan_to_json(xpub, gap_limit));
+
+    let c_json = match CString::new(json) {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+
+    *out_json = c_json.into_raw();
+    0
+}
+
+async fn scan_to_json(xpub: String, gap_limit: usize) -> String {
+    let backend: Arc<dyn BalanceBackend> = Arc::new(FailoverBackend::new(vec![
+        Box::new(EsploraBackend::blockstream()),
+        Box::new(EsploraBackend::mempool_space()),
+    ]));
+
+    match check_balances(&xpub, gap_limit, ScriptType::P2wpkh, backend, None).await {
+        Ok(result) => serde_json::json!({
+            "complete": result.complete,
+            "error": result.error,
+            "balances": result.balances.iter().map(|b| serde_json::json!({
+                "address": b.address,
+                "balance": b.balance,
+                "derivation_path": b.derivation_path,
+                "utxos": b.utxos.iter().map(|u| serde_json::json!({
+                    "txid": u.txid,
+                    "vout": u.vout,
+                    "value": u.value,
+                    "confirmed": u.confirmed,
+                    "block_height": u.block_height,
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        })
+        .to_string(),
+        Err(e) => serde_json::json!({ "error": e }).to_string(),
+    }
+}
+
+/// Frees a string previously returned by [`wbt_check_balances`].
+///
+/// # Safety
+/// `ptr` must either be null (a no-op) or a pointer previously returned by
+/// [`wbt_check_balances`], and must not be used again after this call.
+#[no_mangle]
+pub unsafe extern "C" fn wbt_string_free(ptr: *mut c_char) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(CString::from_raw(ptr));
+}
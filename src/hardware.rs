@@ -0,0 +1,99 @@
+//! Hardware-wallet import, gated behind the `hardware` feature so the
+//! default build doesn't need a device bridge installed. Speaks to
+//! connected devices through an HWI-style subprocess bridge (the `hwi`
+//! Python tool, or anything that implements the same `enumerate`/`getxpub`
+//! JSON contract) rather than talking to transports directly, so Ledger,
+//! Trezor, and other HWI-supported devices all work through one path.
+
+use std::process::Command;
+
+use crate::descriptor::ScriptType;
+
+/// A hardware wallet enumerated by the HWI bridge.
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub fingerprint: String,
+    pub model: String,
+}
+
+/// Lists currently-connected hardware wallets by shelling out to `hwi
+/// enumerate` and parsing its JSON array of device descriptors.
+pub fn list_devices() -> Result<Vec<Device>, String> {
+    let output = Command::new("hwi")
+        .arg("enumerate")
+        .output()
+        .map_err(|e| format!("Failed to run hwi: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "hwi enumerate failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse hwi output: {}", e))?;
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let fingerprint = entry
+                .get("fingerprint")
+                .and_then(|v| v.as_str())
+                .ok_or("hwi device entry missing fingerprint")?
+                .to_string();
+            let model = entry
+                .get("type")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string();
+            Ok(Device { fingerprint, model })
+        })
+        .collect()
+}
+
+/// The standard BIP44/49/84/86 account path (mainnet, account 0) for a
+/// given script type, e.g. `m/84h/0h/0h` for native SegWit.
+pub fn account_path(script_type: ScriptType) -> &'static str {
+    match script_type {
+        ScriptType::P2pkh => "m/44h/0h/0h",
+        ScriptType::P2shP2wpkh => "m/49h/0h/0h",
+        ScriptType::P2wpkh => "m/84h/0h/0h",
+        ScriptType::P2tr => "m/86h/0h/0h",
+    }
+}
+
+/// Exports the account-level extended public key at `path` from `device` by
+/// calling `hwi -f <fingerprint> getxpub <path>`.
+pub fn export_xpub(device: &Device, path: &str) -> Result<String, String> {
+    let output = Command::new("hwi")
+        .args(["-f", &device.fingerprint, "getxpub", path])
+        .output()
+        .map_err(|e| format!("Failed to run hwi: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "hwi getxpub failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let response: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse hwi output: {}", e))?;
+
+    response
+        .get("xpub")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| "hwi response did not contain an xpub".to_string())
+}
+
+/// Convenience wrapper for the GUI: picks the first connected device and
+/// exports its account xpub for `script_type`.
+pub fn import_xpub(script_type: ScriptType) -> Result<String, String> {
+    let devices = list_devices()?;
+    let device = devices
+        .first()
+        .ok_or("No hardware wallet detected. Is it connected and unlocked?")?;
+    export_xpub(device, account_path(script_type))
+}
@@ -0,0 +1,13 @@
+//! Core wallet-scanning engine, usable headlessly (CLI tools, test
+//! harnesses, the [`ffi`] layer) without pulling in `iced`/`tokio`'s GUI
+//! executor. The GUI binary (`main.rs`) is a thin consumer of this crate.
+
+pub mod api;
+pub mod backend;
+pub mod descriptor;
+pub mod electrum;
+pub mod ffi;
+#[cfg(feature = "hardware")]
+pub mod hardware;
+pub mod utils;
+pub mod wallet;
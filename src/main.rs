@@ -1,9 +1,6 @@
 mod app;
 mod executor;
 mod messages;
-mod wallet;
-mod api;
-mod utils;
 
 use iced::{Application, Settings};
 use app::WalletBalanceApp;
@@ -1,7 +1,20 @@
-#[derive(Debug, Clone)]
-pub enum Message {
-    XpubInputChanged(String),
-    CheckBalance,
-    LoadMore,
-    BalanceResult(Result<Vec<crate::wallet::AddressBalance>, String>),
-}
+use wallet_balance_tool::{backend, descriptor, wallet};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    XpubInputChanged(String),
+    GapLimitChanged(String),
+    ScriptTypeSelected(descriptor::ScriptType),
+    BackendSelected(backend::BackendChoice),
+    ElectrumServerChanged(String),
+    ToggleUtxos(usize),
+    CheckBalance,
+    BalanceResult(Result<wallet::ScanResult, String>),
+    /// Running count of addresses checked so far, relayed from the scan
+    /// task via the channel in [`crate::app::WalletBalanceApp::subscription`].
+    ScanProgress(usize),
+    #[cfg(feature = "hardware")]
+    ImportFromDevice,
+    #[cfg(feature = "hardware")]
+    DeviceXpubImported(Result<String, String>),
+}
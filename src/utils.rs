@@ -1,21 +1,72 @@
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{Duration, Instant};
-use tokio::time::sleep;
-
-static LAST_REQUEST: AtomicU64 = AtomicU64::new(0);
-const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(250);
-
-pub async fn enforce_rate_limit() {
-    let last = LAST_REQUEST.load(Ordering::Relaxed);
-    let now = Instant::now().elapsed().as_millis() as u64;
-    let elapsed = now.saturating_sub(last);
-
-    if elapsed < MIN_REQUEST_INTERVAL.as_millis() as u64 {
-        sleep(Duration::from_millis(
-            MIN_REQUEST_INTERVAL.as_millis() as u64 - elapsed,
-        ))
-        .await;
-    }
-
-    LAST_REQUEST.store(now, Ordering::Relaxed);
-}
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tokio::time::sleep;
+
+/// Requests/sec budget shared across every in-flight address lookup.
+pub const DEFAULT_REQUESTS_PER_SECOND: u64 = 4;
+/// Number of address lookups allowed to be in flight at once.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// A token-bucket rate limiter plus a concurrency cap. The two are
+/// independent: `concurrency` only bounds how many callers can be past the
+/// gate at once, while `last_request` -- guarded by its own `Mutex`, held
+/// across the sleep -- serializes the actual interval check so concurrent
+/// callers can't all read the same timestamp and sleep the same amount (a
+/// check-then-act race that would let `concurrency` requests through in a
+/// single interval window instead of one).
+struct RateLimiter {
+    min_interval: Duration,
+    last_request: Mutex<Option<Instant>>,
+    concurrency: Semaphore,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u64, concurrency: usize) -> Self {
+        Self {
+            min_interval: Duration::from_millis(1000 / requests_per_second.max(1)),
+            last_request: Mutex::new(None),
+            concurrency: Semaphore::new(concurrency.max(1)),
+        }
+    }
+
+    /// Waits for a free concurrency slot, then for whatever is left of the
+    /// minimum interval since the last request. Holding the returned permit
+    /// for the duration of the gated call is what enforces the concurrency
+    /// cap; dropping it lets the next queued lookup through.
+    async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .concurrency
+            .acquire()
+            .await
+            .expect("rate limiter semaphore is never closed");
+
+        // Held across the sleep so the read-sleep-write sequence below is
+        // atomic with respect to every other concurrent caller.
+        let mut last_request = self.last_request.lock().await;
+        let now = Instant::now();
+        if let Some(last) = *last_request {
+            let elapsed = now.saturating_duration_since(last);
+            if elapsed < self.min_interval {
+                sleep(self.min_interval - elapsed).await;
+            }
+        }
+        *last_request = Some(Instant::now());
+        drop(last_request);
+
+        permit
+    }
+}
+
+fn rate_limiter() -> &'static RateLimiter {
+    static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+    LIMITER.get_or_init(|| RateLimiter::new(DEFAULT_REQUESTS_PER_SECOND, DEFAULT_CONCURRENCY))
+}
+
+/// Waits for both a free concurrency slot and the shared per-request rate
+/// budget. The returned permit must be held for the duration of the backend
+/// call it's gating -- dropping it early (e.g. `let _ = enforce_rate_limit()`)
+/// defeats the concurrency cap.
+pub async fn enforce_rate_limit() -> SemaphorePermit<'static> {
+    rate_limiter().acquire().await
+}
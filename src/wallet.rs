@@ -1,119 +1,346 @@
-use bitcoin::{
-    base58, bip32::{ChildNumber, DerivationPath, ExtendedPubKey}, hashes::{sha256, Hash as BitcoinHash}, Address, Network,
-    PublicKey, secp256k1::Secp256k1,
-};
-use std::str::FromStr;
-
-use crate::api::get_address_balance;
-use crate::utils::enforce_rate_limit;
-
-#[derive(Debug, Clone)]
-pub struct AddressBalance {
-    pub address: String,
-    pub balance: f64,
-    pub derivation_path: String,
-}
-
-pub async fn check_balances(
-    xpub: &str,
-    start_idx: usize,
-    end_idx: usize,
-) -> Result<Vec<AddressBalance>, String> {
-    let (network, xpub_to_use) = parse_xpub(xpub)?;
-    let extended_pubkey = ExtendedPubKey::from_str(&xpub_to_use)
-        .map_err(|e| format!("Invalid extended public key: {}", e))?;
-    let secp = Secp256k1::new();
-    let mut balances = Vec::new();
-
-    // Check external addresses
-    for i in start_idx..end_idx {
-        let path = DerivationPath::from_str(&format!("m/0/{}", i))
-            .map_err(|e| format!("Invalid derivation path: {}", e))?;
-        let derived_pubkey = extended_pubkey
-            .derive_pub(&secp, &path)
-            .map_err(|e| format!("Derivation error: {}", e))?;
-        let public_key = PublicKey::new(derived_pubkey.public_key);
-        let address = Address::p2wpkh(&public_key, network)
-            .map_err(|e| format!("Address generation error: {}", e))?
-            .to_string();
-
-        enforce_rate_limit().await;
-
-        let balance = match get_address_balance(&address, network).await {
-            Ok(bal) => bal,
-            Err(e) if e.contains("rate limit") || e.contains("exceeded") => {
-                return Ok(balances);
-            }
-            Err(e) => return Err(e),
-        };
-
-        balances.push(AddressBalance {
-            address,
-            balance,
-            derivation_path: path.to_string(),
-        });
-    }
-
-    // Check change address
-    let change_idx = start_idx / 10;
-    let path = DerivationPath::from_str(&format!("m/1/{}", change_idx))
-        .map_err(|e| format!("Invalid derivation path: {}", e))?;
-    let derived_pubkey = extended_pubkey
-        .derive_pub(&secp, &path)
-        .map_err(|e| format!("Derivation error: {}", e))?;
-    let public_key = PublicKey::new(derived_pubkey.public_key);
-    let address = Address::p2wpkh(&public_key, network)
-        .map_err(|e| format!("Address generation error: {}", e))?
-        .to_string();
-
-    enforce_rate_limit().await;
-
-    let balance = match get_address_balance(&address, network).await {
-        Ok(bal) => bal,
-        Err(e) if e.contains("rate limit") || e.contains("exceeded") => {
-            return Ok(balances);
-        }
-        Err(e) => return Err(e),
-    };
-
-    balances.push(AddressBalance {
-        address,
-        balance,
-        derivation_path: path.to_string(),
-    });
-
-    Ok(balances)
-}
-
-fn parse_xpub(xpub: &str) -> Result<(Network, String), String> {
-    if xpub.starts_with("vpub") {
-        let decoded = base58::from_check(xpub)
-            .map_err(|e| format!("Failed to decode vpub: {}", e))?;
-
-        if decoded.len() < 78 {
-            return Err("Invalid extended public key length".to_string());
-        }
-
-        // Extract the key material (everything except version and checksum)
-        let key_material = &decoded[4..decoded.len() - 4];
-
-        // Create new vector with tpub version bytes
-        let mut modified = Vec::with_capacity(78);
-        modified.extend_from_slice(&[0x04, 0x35, 0x87, 0xCF]); // tpub version
-        modified.extend_from_slice(key_material);
-        
-        // Calculate double SHA256 checksum
-        let hash1 = sha256::Hash::hash(&modified);
-        let hash2 = sha256::Hash::hash(hash1.as_ref());
-        let checksum = hash2[..4].to_vec(); // checksum now owns the data
-        modified.extend_from_slice(&checksum);
-
-        let tpub = base58::encode_check(&modified);
-
-        Ok((Network::Testnet, tpub))
-    } else if xpub.starts_with("xpub") {
-        Ok((Network::Bitcoin, xpub.to_string()))
-    } else {
-        Err("Unsupported extended public key format. Must start with 'xpub' or 'vpub'".to_string())
-    }
-}
+use bitcoin::{
+    bip32::{DerivationPath, ExtendedPubKey},
+    secp256k1::{All, Secp256k1},
+    Network, PublicKey,
+};
+use futures::stream::{self, StreamExt};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::backend::{BalanceBackend, Utxo};
+use crate::descriptor::{self, ScriptType};
+use crate::utils::{enforce_rate_limit, DEFAULT_CONCURRENCY};
+
+/// Number of consecutive unused addresses on a branch before we stop
+/// scanning it, per BIP-44.
+pub const DEFAULT_GAP_LIMIT: usize = 20;
+
+/// A single derived address's lookup result, carrying enough to both extend
+/// `balances` and update the gap-limit counter once collected back in order.
+struct AddressLookup {
+    balance: AddressBalance,
+    used: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AddressBalance {
+    pub address: String,
+    pub balance: f64,
+    pub derivation_path: String,
+    pub utxos: Vec<Utxo>,
+}
+
+/// Result of a gap-limit scan across the external and change branches.
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+    /// Every address balance found before scanning stopped -- populated
+    /// even when `complete` is `false`, so a backend failure partway
+    /// through a long scan doesn't throw away everything found so far.
+    pub balances: Vec<AddressBalance>,
+    /// `true` if both branches were scanned until `gap_limit` consecutive
+    /// unused addresses were found; `false` if scanning was cut short (e.g.
+    /// a backend failed or was rate-limited) and `balances` may be
+    /// incomplete.
+    pub complete: bool,
+    /// Set when `complete` is `false` because of an error (as opposed to
+    /// simply reaching a rate limit, which isn't itself a failure worth
+    /// surfacing). `None` when `complete` is `true`.
+    pub error: Option<String>,
+}
+
+/// Scans `input` (a bare xpub/ypub/zpub/... or a full output descriptor)
+/// for balances against `backend`. `selected_script_type` is used when
+/// `input` doesn't itself specify one, i.e. it's a bare `xpub`/`tpub` rather
+/// than a descriptor or a script-specific prefix like `zpub`. If `progress`
+/// is given, the running count of addresses checked so far is sent on it
+/// after every address, so a caller (e.g. the GUI) can reflect real
+/// completion instead of a static estimate.
+///
+/// Only returns `Err` for failures before any scanning starts (a malformed
+/// `input` or extended public key). Once scanning is underway, a backend
+/// failure stops the scan but is reported via `ScanResult::error` alongside
+/// whatever balances were already found, rather than discarding them.
+pub async fn check_balances(
+    input: &str,
+    gap_limit: usize,
+    selected_script_type: ScriptType,
+    backend: Arc<dyn BalanceBackend>,
+    progress: Option<UnboundedSender<usize>>,
+) -> Result<ScanResult, String> {
+    let parsed = descriptor::parse(input)?;
+    let (network, xpub_to_use, slip132_hint) = descriptor::normalize_xpub(&parsed.xpub)?;
+    let script_type = parsed.script_type.or(slip132_hint).unwrap_or(selected_script_type);
+
+    let extended_pubkey = ExtendedPubKey::from_str(&xpub_to_use)
+        .map_err(|e| format!("Invalid extended public key: {}", e))?;
+    let secp = Secp256k1::new();
+    let mut balances = Vec::new();
+
+    // External (receive) and change branches are scanned independently, each
+    // with its own gap-limit counter.
+    for branch in [0u32, 1u32] {
+        let outcome = scan_branch(
+            &extended_pubkey,
+            &secp,
+            network,
+            script_type,
+            branch,
+            gap_limit,
+            backend.as_ref(),
+            &mut balances,
+            progress.as_ref(),
+        )
+        .await;
+
+        match outcome {
+            Ok(true) => {}
+            Ok(false) => {
+                return Ok(ScanResult {
+                    balances,
+                    complete: false,
+                    error: None,
+                });
+            }
+            // `balances` already holds everything found before this branch
+            // failed, since `scan_branch` pushes into it as it goes.
+            Err(e) => {
+                return Ok(ScanResult {
+                    balances,
+                    complete: false,
+                    error: Some(e),
+                });
+            }
+        }
+    }
+
+    Ok(ScanResult {
+        balances,
+        complete: true,
+        error: None,
+    })
+}
+
+/// Scans a single branch (`0` = external, `1` = change) starting at index 0
+/// until `gap_limit` consecutive unused addresses are seen. Returns `Ok(true)`
+/// if the branch was scanned to completion, or `Ok(false)` if scanning had to
+/// stop early because the backend reported a rate limit. Any other backend
+/// error (e.g. every configured backend unreachable) is returned as `Err`.
+///
+/// Addresses are looked up in batches of up to [`DEFAULT_CONCURRENCY`],
+/// pipelined through `buffered` so several lookups are in flight at once,
+/// with every individual backend call -- not just the first per address --
+/// gated by the shared rate limiter in [`enforce_rate_limit`]. Each batch is
+/// awaited and folded into `consecutive_unused` in derivation order, since
+/// the gap-limit stopping condition depends on that order.
+async fn scan_branch(
+    extended_pubkey: &ExtendedPubKey,
+    secp: &Secp256k1<All>,
+    network: Network,
+    script_type: ScriptType,
+    branch: u32,
+    gap_limit: usize,
+    backend: &dyn BalanceBackend,
+    balances: &mut Vec<AddressBalance>,
+    progress: Option<&UnboundedSender<usize>>,
+) -> Result<bool, String> {
+    let mut index = 0u32;
+    let mut consecutive_unused = 0usize;
+
+    while consecutive_unused < gap_limit {
+        let batch_len = DEFAULT_CONCURRENCY.min(gap_limit - consecutive_unused);
+        let batch_indices = index..index + batch_len as u32;
+
+        let lookups: Vec<Result<AddressLookup, String>> = stream::iter(batch_indices.map(|i| {
+            lookup_address(extended_pubkey, secp, network, script_type, branch, i, backend)
+        }))
+        .buffered(DEFAULT_CONCURRENCY)
+        .collect()
+        .await;
+
+        for lookup in lookups {
+            let lookup = match lookup {
+                Ok(lookup) => lookup,
+                // `EsploraBackend` reports an HTTP 429 as "... rate limited
+                // this request (429)" (see `api::check_rate_limited`); a
+                // `FailoverBackend` only surfaces an error once every configured
+                // backend has failed, and then reports the last backend's error
+                // wrapped as "<name>: <inner>", so the substring still matches
+                // when that last backend was rate-limited.
+                Err(e) if e.contains("rate limited") => {
+                    return Ok(false);
+                }
+                Err(e) => return Err(e),
+            };
+
+            if lookup.used {
+                consecutive_unused = 0;
+            } else {
+                consecutive_unused += 1;
+            }
+
+            balances.push(lookup.balance);
+            if let Some(progress) = progress {
+                // A dropped receiver just means the caller stopped caring
+                // about progress, not a scan failure.
+                let _ = progress.send(balances.len());
+            }
+
+            if consecutive_unused >= gap_limit {
+                break;
+            }
+        }
+
+        index += batch_len as u32;
+    }
+
+    Ok(true)
+}
+
+/// Derives a single `m/{branch}/{index}` address and fetches its balance,
+/// tx count, and UTXOs, gated by the shared rate limiter.
+async fn lookup_address(
+    extended_pubkey: &ExtendedPubKey,
+    secp: &Secp256k1<All>,
+    network: Network,
+    script_type: ScriptType,
+    branch: u32,
+    index: u32,
+    backend: &dyn BalanceBackend,
+) -> Result<AddressLookup, String> {
+    let path = DerivationPath::from_str(&format!("m/{}/{}", branch, index))
+        .map_err(|e| format!("Invalid derivation path: {}", e))?;
+    let derived_pubkey = extended_pubkey
+        .derive_pub(secp, &path)
+        .map_err(|e| format!("Derivation error: {}", e))?;
+    let public_key = PublicKey::new(derived_pubkey.public_key);
+    let address = script_type
+        .derive_address(&public_key, network, secp)?
+        .to_string();
+
+    // `get_balance_and_tx_count` covers what would otherwise be two
+    // separate calls (and, for `EsploraBackend`, two redundant fetches of
+    // the same `/address/{addr}` response), so it only needs one permit.
+    let permit = enforce_rate_limit().await;
+    let (balance, tx_count) = backend.get_balance_and_tx_count(&address, network).await?;
+    drop(permit);
+
+    // Only used addresses can have UTXOs, so skip the extra round-trip for
+    // addresses we already know are empty.
+    let utxos = if tx_count > 0 {
+        let permit = enforce_rate_limit().await;
+        let utxos = backend.get_utxos(&address, network).await?;
+        drop(permit);
+        utxos
+    } else {
+        Vec::new()
+    };
+
+    Ok(AddressLookup {
+        balance: AddressBalance {
+            address,
+            balance,
+            derivation_path: path.to_string(),
+            utxos,
+        },
+        used: tx_count > 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::HashSet;
+    use std::sync::Mutex as StdMutex;
+
+    // BIP32 test vector 1 master xpub -- a real point on the curve, but not
+    // tied to any funds; only used here so `derive_pub` has a valid key to
+    // work with.
+    const TEST_XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+
+    /// Reports a fixed set of addresses as having on-chain history (tx
+    /// count 1) and everything else as unused, so the gap-limit counter in
+    /// `scan_branch` can be exercised deterministically without any network
+    /// access. Also records every address queried, to check scanning
+    /// stopped exactly where expected rather than over- or under-fetching.
+    struct FixedUsageBackend {
+        used: HashSet<String>,
+        queried: StdMutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl BalanceBackend for FixedUsageBackend {
+        fn name(&self) -> &str {
+            "fixed-usage"
+        }
+
+        async fn get_balance(&self, _address: &str, _network: Network) -> Result<f64, String> {
+            Ok(0.0)
+        }
+
+        async fn get_tx_count(&self, address: &str, _network: Network) -> Result<u64, String> {
+            self.queried.lock().unwrap().push(address.to_string());
+            Ok(if self.used.contains(address) { 1 } else { 0 })
+        }
+
+        async fn get_utxos(&self, _address: &str, _network: Network) -> Result<Vec<Utxo>, String> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Derives the same `m/0/{index}` external address `scan_branch` would,
+    /// so the test backend can be pre-seeded with the exact addresses it
+    /// will be queried for.
+    fn external_address(index: u32) -> String {
+        let secp = Secp256k1::new();
+        let extended_pubkey = ExtendedPubKey::from_str(TEST_XPUB).unwrap();
+        let path = DerivationPath::from_str(&format!("m/0/{}", index)).unwrap();
+        let derived = extended_pubkey.derive_pub(&secp, &path).unwrap();
+        let public_key = PublicKey::new(derived.public_key);
+        ScriptType::P2wpkh
+            .derive_address(&public_key, Network::Bitcoin, &secp)
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn scan_branch_stops_after_gap_limit_consecutive_unused() {
+        let gap_limit = 3;
+        // Indices 0 and 1 are "used"; every index from 2 onward is not, so
+        // the branch should stop right after `gap_limit` consecutive unused
+        // addresses, i.e. at index `1 + gap_limit`.
+        let used: HashSet<String> = (0..2).map(external_address).collect();
+        let backend = FixedUsageBackend {
+            used,
+            queried: StdMutex::new(Vec::new()),
+        };
+
+        let secp = Secp256k1::new();
+        let extended_pubkey = ExtendedPubKey::from_str(TEST_XPUB).unwrap();
+        let mut balances = Vec::new();
+
+        let complete = scan_branch(
+            &extended_pubkey,
+            &secp,
+            Network::Bitcoin,
+            ScriptType::P2wpkh,
+            0,
+            gap_limit,
+            &backend,
+            &mut balances,
+            None,
+        )
+        .await
+        .expect("scan should succeed");
+
+        assert!(complete);
+        // 2 used addresses, then exactly `gap_limit` consecutive unused ones.
+        assert_eq!(balances.len(), 2 + gap_limit);
+        assert_eq!(backend.queried.lock().unwrap().len(), 2 + gap_limit);
+        assert_eq!(
+            balances.last().unwrap().derivation_path,
+            format!("m/0/{}", 1 + gap_limit)
+        );
+    }
+}